@@ -1,14 +1,30 @@
-use std::{collections::HashMap, fs::File, io::BufWriter, io::Write, path::PathBuf, time::Instant};
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    io::{BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicIsize, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
 
 use casper_execution_engine::{
-    shared::newtypes::CorrelationId, storage::trie::Trie, storage::trie_store::DeleteResult,
+    core::engine_state::EngineState, shared::newtypes::CorrelationId,
+    storage::global_state::lmdb::LmdbGlobalState, storage::trie::Trie,
+    storage::trie_store::DeleteResult,
 };
 use casper_hashing::Digest;
 use casper_types::{bytesrepr::FromBytes, Key, StoredValue};
-use lmdb::{Cursor, DatabaseFlags, Environment, EnvironmentFlags, Transaction};
+use lmdb::{Cursor, DatabaseFlags, Environment, EnvironmentFlags, Transaction, WriteFlags};
 use retrieve_state::storage;
 use structopt::StructOpt;
 
+mod trie_source;
+
+use trie_source::{LmdbSource, RocksDbSource, TrieSource};
+
 #[derive(Debug, StructOpt)]
 struct Opts {
     #[structopt(short = "p", name = "Path to LMDB data file.")]
@@ -38,25 +54,187 @@ struct Opts {
         name = "State root hex (optional). If passed it will gather stats only for the given state root."
     )]
     state_root_hex: Option<String>,
+
+    #[structopt(
+        long = "gc",
+        help = "Run mark-and-sweep garbage collection over TRIE_STORE instead of gathering stats. Requires at least one --retain-root."
+    )]
+    gc: bool,
+
+    #[structopt(
+        long = "retain-root",
+        help = "State root hex to retain during --gc (repeatable). Every node reachable from a retained root is kept; everything else is swept."
+    )]
+    retain_roots: Vec<String>,
+
+    #[structopt(
+        long = "backend",
+        default_value = "lmdb",
+        help = "Storage backend the trie lives in: \"lmdb\" or \"rocksdb\". --gc is currently lmdb-only."
+    )]
+    backend: String,
+
+    #[structopt(
+        short = "q",
+        long = "quiet",
+        help = "Suppress periodic progress reporting during long trie walks."
+    )]
+    quiet: bool,
+
+    #[structopt(
+        long = "export",
+        help = "Export every node reachable from the state root into a compacted, deduplicated LMDB TRIE_STORE at this path."
+    )]
+    export: Option<PathBuf>,
+
+    #[structopt(
+        long = "threads",
+        default_value = "1",
+        help = "Number of worker threads to use for the TRIE_STORE walk (lmdb backend only). Each worker holds its own read transaction against a shared work queue."
+    )]
+    threads: usize,
+
+    #[structopt(
+        long = "checkpoint",
+        help = "Periodically persist traversal progress to this file and resume from it on startup if present. Sequential TRIE_STORE walk only. Not yet supported together with --export."
+    )]
+    checkpoint: Option<PathBuf>,
 }
 
+/// Set by the SIGINT handler registered when `--checkpoint` is in use; the
+/// traversal loop polls this and writes a final checkpoint before exiting.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// How often to print a progress line: whenever the visited-node count reaches
+/// a multiple of this, or once `PROGRESS_INTERVAL` has passed since the last
+/// line, whichever comes first.
+const PROGRESS_EVERY_NODES: usize = 50_000;
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How often the sequential TRIE_STORE walk checkpoints itself when
+/// `--checkpoint` is set, independent of the `INTERRUPTED` flag.
+const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(60);
+
 #[tokio::main]
 async fn main() -> Result<(), anyhow::Error> {
     let start = Instant::now();
     let opts = Opts::from_args();
-    let env = Environment::new()
-        // Set the flag to manage our own directory like in the storage component.
-        .set_flags(EnvironmentFlags::NO_SUB_DIR)
-        .set_max_dbs(1)
-        .open(&opts.lmdb_path)?;
-
-    let db = env.create_db(Some(&opts.db_name), DatabaseFlags::empty())?;
     println!(
-        "Scanning LMDB data file: {:?}\ndatabase name: {}, state root: {:?}",
-        opts.lmdb_path, opts.db_name, opts.state_root_hex,
+        "Scanning data file: {:?}\ndatabase name: {}, state root: {:?}, backend: {}",
+        opts.lmdb_path, opts.db_name, opts.state_root_hex, opts.backend,
     );
 
-    let txn = env.begin_ro_txn()?;
+    if opts.checkpoint.is_some() {
+        assert!(
+            opts.export.is_none(),
+            "--export is not yet supported together with --checkpoint: a checkpoint's frontier \
+             can race ahead of what's been flushed to the export destination, so a hard kill \
+             between periodic checkpoints can resume a walk that permanently skips re-exporting \
+             nodes visited just before it died"
+        );
+    }
+
+    if opts.gc {
+        assert_eq!(
+            opts.db_name, "TRIE_STORE",
+            "--gc only operates on TRIE_STORE"
+        );
+        assert_eq!(
+            opts.backend, "lmdb",
+            "--gc currently only supports the lmdb backend"
+        );
+        let env = open_lmdb_env(&opts.lmdb_path)?;
+        let db = env.create_db(Some(&opts.db_name), DatabaseFlags::empty())?;
+        let retain_roots: Vec<Digest> = opts
+            .retain_roots
+            .iter()
+            .map(|hex| Digest::from_hex(hex).expect("error parsing retain root hex"))
+            .collect();
+        if retain_roots.is_empty() {
+            panic!("--gc requires at least one --retain-root");
+        }
+        run_gc(&env, db, &retain_roots)?;
+        println!("done in {:?}", start.elapsed());
+        return Ok(());
+    }
+
+    if opts.threads > 1 {
+        assert_eq!(
+            opts.db_name, "TRIE_STORE",
+            "--threads > 1 only applies to TRIE_STORE"
+        );
+        assert_eq!(
+            opts.backend, "lmdb",
+            "--threads > 1 currently only supports the lmdb backend"
+        );
+        assert!(
+            opts.export.is_none(),
+            "--export is not yet supported together with --threads > 1"
+        );
+        let state_root_hex = opts
+            .state_root_hex
+            .clone()
+            .expect("TRIE_STORE requires a state root hash to be passed.");
+        let state_root = Digest::from_hex(&state_root_hex).expect("error parsing state root hex");
+
+        let env = open_lmdb_env(&opts.lmdb_path)?;
+        let db = env.create_db(Some(&opts.db_name), DatabaseFlags::empty())?;
+
+        let walk = run_parallel_trie_walk(&env, db, state_root, opts.threads, opts.quiet, &start);
+        println!(
+            "parallel walk: {} node(s) visited, {} leaves, {} bytes, {} thread(s), {:?}",
+            walk.visited_nodes,
+            walk.leaves_seen,
+            walk.bytes_processed,
+            opts.threads,
+            start.elapsed()
+        );
+
+        // EraInfo deletion mutates the trie root, so it stays a single-threaded
+        // pass over the keys the workers collected, run after the parallel walk.
+        let mut deleted_era_info = 0;
+        if !walk.era_info_keys.is_empty() {
+            let (mut engine_state, _env) = storage::load_execution_engine(
+                opts.lmdb_path.clone(),
+                retrieve_state::DEFAULT_MAX_DB_SIZE,
+                state_root,
+                true,
+            )?;
+            let mut new_root_hash = state_root;
+            for trie_key in walk.era_info_keys {
+                new_root_hash = delete_era_info_key(&mut engine_state, new_root_hash, trie_key);
+                deleted_era_info += 1;
+            }
+        }
+        println!("deleted {deleted_era_info} era info entries.");
+
+        let filename = format!("trie_report-{}.csv", state_root_hex);
+        println!("Will write trie report for state root to {}", filename);
+        write_trie_report(
+            &filename,
+            &walk.key_tags,
+            &walk.stored_value_tags,
+            &walk.trie_lengths,
+        )
+        .unwrap();
+
+        println!("processed 1 db records total");
+        return Ok(());
+    }
+
+    let source: Box<dyn TrieSource> = match opts.backend.as_str() {
+        "lmdb" => {
+            let env = open_lmdb_env(&opts.lmdb_path)?;
+            let db = env.create_db(Some(&opts.db_name), DatabaseFlags::empty())?;
+            Box::new(LmdbSource::new(env, db))
+        }
+        "rocksdb" => Box::new(RocksDbSource::open(&opts.lmdb_path)?),
+        other => panic!(
+            "unsupported backend {:?}, expected \"lmdb\" or \"rocksdb\"",
+            other
+        ),
+    };
+
     let mut record_count = 0;
     let mut largest_record = 0;
 
@@ -72,29 +250,123 @@ async fn main() -> Result<(), anyhow::Error> {
         let state_root = Digest::from_hex(&state_root_hex).expect("error parsing state root hex");
 
         let filename = format!("trie_report-{}.csv", state_root_hex);
-        println!("Will write trie report for state root to {}", filename);
-        let mut report_writer = BufWriter::new(File::create(filename).unwrap());
 
         let mut unvisited_nodes = vec![state_root];
         let mut deleted_era_info = 0;
         let mut new_root_hash = state_root.clone();
-        let (engine_state, _env) = storage::load_execution_engine(
-            opts.lmdb_path,
-            retrieve_state::DEFAULT_MAX_DB_SIZE,
-            new_root_hash,
-            true,
-        )?;
-        while let Some(digest) = unvisited_nodes.pop() {
-            let bytes = txn
-                .get(db, &digest)
+
+        if opts.checkpoint.is_some() {
+            ctrlc::set_handler(|| INTERRUPTED.store(true, Ordering::SeqCst))
+                .expect("unable to register SIGINT handler");
+        }
+
+        if let Some(checkpoint_path) = &opts.checkpoint {
+            if checkpoint_path.exists() {
+                let checkpoint = read_checkpoint(checkpoint_path)?;
+                assert_eq!(
+                    checkpoint.state_root, state_root,
+                    "checkpoint at {:?} belongs to a different state root; refusing to resume",
+                    checkpoint_path
+                );
+                println!(
+                    "resuming from checkpoint {:?}: {} node(s) in frontier, {} era info entries already deleted",
+                    checkpoint_path,
+                    checkpoint.unvisited_nodes.len(),
+                    checkpoint.deleted_era_info
+                );
+                unvisited_nodes = checkpoint.unvisited_nodes;
+                deleted_era_info = checkpoint.deleted_era_info;
+                new_root_hash = checkpoint.new_root_hash;
+                key_tags = checkpoint.key_tags;
+                stored_value_tags = checkpoint.stored_value_tags;
+                trie_lengths = checkpoint.trie_lengths;
+            }
+        }
+
+        let mut visited_nodes = 0usize;
+        let mut leaves_seen = 0usize;
+        let mut bytes_processed = 0usize;
+        let mut last_progress = Instant::now();
+        let mut last_checkpoint = Instant::now();
+
+        let export_target = match &opts.export {
+            Some(path) => {
+                println!("Will export compacted trie snapshot to {:?}", path);
+                let dest_env = Environment::new()
+                    .set_flags(EnvironmentFlags::NO_SUB_DIR)
+                    .set_max_dbs(1)
+                    .set_map_size(retrieve_state::DEFAULT_MAX_DB_SIZE)
+                    .open(path)?;
+                let dest_db = dest_env.create_db(Some("TRIE_STORE"), DatabaseFlags::empty())?;
+                Some((dest_env, dest_db))
+            }
+            None => None,
+        };
+        let mut export_buffer: Vec<(Digest, Vec<u8>)> = Vec::new();
+        const EXPORT_BATCH_SIZE: usize = 5_000;
+
+        // EraInfo deletion mutates the trie through the execution engine, which is
+        // only wired up for an LMDB-backed store.
+        let mut engine_state = if opts.backend == "lmdb" {
+            let (engine_state, _env) = storage::load_execution_engine(
+                opts.lmdb_path.clone(),
+                retrieve_state::DEFAULT_MAX_DB_SIZE,
+                new_root_hash,
+                true,
+            )?;
+            Some(engine_state)
+        } else {
+            None
+        };
+
+        loop {
+            if INTERRUPTED.load(Ordering::SeqCst) {
+                if let Some((dest_env, dest_db)) = &export_target {
+                    flush_export(dest_env, *dest_db, &mut export_buffer)?;
+                }
+                if let Some(checkpoint_path) = &opts.checkpoint {
+                    write_checkpoint(
+                        checkpoint_path,
+                        state_root,
+                        new_root_hash,
+                        deleted_era_info,
+                        &unvisited_nodes,
+                        &key_tags,
+                        &stored_value_tags,
+                        &trie_lengths,
+                    )?;
+                    println!(
+                        "interrupted: wrote checkpoint to {:?}, exiting",
+                        checkpoint_path
+                    );
+                }
+                return Ok(());
+            }
+
+            let digest = match unvisited_nodes.pop() {
+                Some(digest) => digest,
+                None => break,
+            };
+            let bytes = source
+                .get(&digest)
                 .expect("provided state root does not exist in database");
 
             let byte_len = bytes.len();
+            visited_nodes += 1;
+            bytes_processed += byte_len;
             if largest_record < byte_len {
                 println!("Found new largest trie DB entry with len {}", byte_len);
                 largest_record = byte_len;
             }
-            let (trie_node, _remainder) = Trie::<Key, StoredValue>::from_bytes(bytes)
+
+            if let Some((dest_env, dest_db)) = &export_target {
+                export_buffer.push((digest, bytes.clone()));
+                if export_buffer.len() >= EXPORT_BATCH_SIZE {
+                    flush_export(dest_env, *dest_db, &mut export_buffer)?;
+                }
+            }
+
+            let (trie_node, _remainder) = Trie::<Key, StoredValue>::from_bytes(&bytes)
                 .expect("unable to deserialize trie node");
 
             match trie_node {
@@ -102,6 +374,7 @@ async fn main() -> Result<(), anyhow::Error> {
                     key: trie_key,
                     value: trie_value,
                 } => {
+                    leaves_seen += 1;
                     log_trie_leaf_stats(
                         trie_key,
                         trie_value,
@@ -116,20 +389,17 @@ async fn main() -> Result<(), anyhow::Error> {
                         // for any newer -> hit stable key
                         // older -> use legacy
 
-                        match engine_state.delete_key(
-                            CorrelationId::new(),
-                            new_root_hash,
-                            &trie_key,
-                        ) {
-                            Ok(DeleteResult::Deleted(root)) => {
+                        match &mut engine_state {
+                            Some(engine_state) => {
+                                new_root_hash =
+                                    delete_era_info_key(engine_state, new_root_hash, trie_key);
                                 deleted_era_info += 1;
-                                new_root_hash = root;
-                            }
-                            Ok(delete) => {
-                                panic!("failed to delete key {:?} - {:?}", trie_key, delete)
                             }
-                            err => {
-                                panic!("failed to delete key {:?} - {:?}", trie_key, err)
+                            None => {
+                                println!(
+                                    "skipping EraInfo deletion for {:?}: not supported on backend {:?}",
+                                    trie_key, opts.backend
+                                );
                             }
                         }
                     }
@@ -142,44 +412,61 @@ async fn main() -> Result<(), anyhow::Error> {
                 ),
                 Trie::Extension { affix: _, pointer } => unvisited_nodes.push(pointer.into_hash()),
             }
-        }
-        record_count += 1;
-
-        println!("deleted {deleted_era_info} era info entries.");
 
-        writeln!(report_writer, "key_tag, count").unwrap();
+            if !opts.quiet
+                && (visited_nodes % PROGRESS_EVERY_NODES == 0
+                    || last_progress.elapsed() >= PROGRESS_INTERVAL)
+            {
+                println!(
+                    "progress: visited={} leaves={} frontier={} bytes={} elapsed={:?}",
+                    visited_nodes,
+                    leaves_seen,
+                    unvisited_nodes.len(),
+                    bytes_processed,
+                    start.elapsed()
+                );
+                last_progress = Instant::now();
+            }
 
-        for (key_tag, count) in key_tags {
-            writeln!(report_writer, "\"{}\", {}", key_tag, count).unwrap();
+            if let Some(checkpoint_path) = &opts.checkpoint {
+                if last_checkpoint.elapsed() >= CHECKPOINT_INTERVAL {
+                    write_checkpoint(
+                        checkpoint_path,
+                        state_root,
+                        new_root_hash,
+                        deleted_era_info,
+                        &unvisited_nodes,
+                        &key_tags,
+                        &stored_value_tags,
+                        &trie_lengths,
+                    )?;
+                    last_checkpoint = Instant::now();
+                }
+            }
         }
+        record_count += 1;
 
-        writeln!(report_writer, "stored_value_tag, count").unwrap();
-        for (stored_value_tag, count) in stored_value_tags {
-            writeln!(report_writer, "\"{}\", {}", stored_value_tag, count).unwrap();
-        }
+        println!("deleted {deleted_era_info} era info entries.");
 
-        writeln!(
-            report_writer,
-            "key_tag, stored_value_tag, average_len, max_len, total_len"
-        )
-        .unwrap();
-        for ((key_tag, stored_value_tag), lengths) in trie_lengths {
-            if lengths.is_empty() {
-                continue;
+        if let Some((dest_env, dest_db)) = export_target {
+            flush_export(&dest_env, dest_db, &mut export_buffer)?;
+            println!("export complete, verifying against source scan...");
+            let dest_source = LmdbSource::new(dest_env, dest_db);
+            let (verify_key_tags, verify_stored_value_tags) =
+                walk_leaf_tag_counts(&dest_source, state_root);
+            if verify_key_tags == key_tags && verify_stored_value_tags == stored_value_tags {
+                println!("export verified: key_tag/stored_value_tag counts match the source scan");
+            } else {
+                println!(
+                    "WARNING: exported snapshot's key_tag/stored_value_tag counts do not match the source scan"
+                );
             }
-            let total_len = lengths.iter().sum::<usize>();
-            let average_len: usize = total_len / lengths.len();
-            let max_len: usize = *lengths.iter().max().unwrap();
-            writeln!(
-                report_writer,
-                "\"{}\", \"{}\", {}, {}, {}",
-                key_tag, stored_value_tag, average_len, max_len, total_len
-            )
-            .unwrap();
         }
+
+        println!("Will write trie report for state root to {}", filename);
+        write_trie_report(&filename, &key_tags, &stored_value_tags, &trie_lengths).unwrap();
     } else {
-        let mut cursor = txn.open_ro_cursor(db)?;
-        for (_key, value) in cursor.iter() {
+        for (_key, value) in source.scan() {
             record_count += 1;
             let serialized_len = value.len();
             if largest_record < serialized_len {
@@ -194,6 +481,468 @@ async fn main() -> Result<(), anyhow::Error> {
     Ok(())
 }
 
+/// Opens the LMDB environment the same way the storage component does:
+/// single-file (no subdirectory) and capped at one database per handle.
+fn open_lmdb_env(path: &Path) -> Result<Environment, anyhow::Error> {
+    let env = Environment::new()
+        .set_flags(EnvironmentFlags::NO_SUB_DIR)
+        .set_max_dbs(1)
+        .open(path)?;
+    Ok(env)
+}
+
+/// Writes a batch of `(digest, bytes)` pairs into `db` and clears `buffer`.
+fn flush_export(
+    env: &Environment,
+    db: lmdb::Database,
+    buffer: &mut Vec<(Digest, Vec<u8>)>,
+) -> Result<(), anyhow::Error> {
+    if buffer.is_empty() {
+        return Ok(());
+    }
+    let mut txn = env.begin_rw_txn()?;
+    for (digest, bytes) in buffer.drain(..) {
+        txn.put(db, &digest, &bytes, WriteFlags::empty())?;
+    }
+    txn.commit()?;
+    Ok(())
+}
+
+/// Writes the `key_tag`/`stored_value_tag`/length-distribution CSV report,
+/// in the same three-section format regardless of whether the stats came
+/// from the sequential or parallel walk.
+fn write_trie_report(
+    filename: &str,
+    key_tags: &HashMap<String, usize>,
+    stored_value_tags: &HashMap<String, usize>,
+    trie_lengths: &HashMap<(String, String), Vec<usize>>,
+) -> std::io::Result<()> {
+    let mut report_writer = BufWriter::new(File::create(filename)?);
+
+    writeln!(report_writer, "key_tag, count")?;
+    for (key_tag, count) in key_tags {
+        writeln!(report_writer, "\"{}\", {}", key_tag, count)?;
+    }
+
+    writeln!(report_writer, "stored_value_tag, count")?;
+    for (stored_value_tag, count) in stored_value_tags {
+        writeln!(report_writer, "\"{}\", {}", stored_value_tag, count)?;
+    }
+
+    writeln!(
+        report_writer,
+        "key_tag, stored_value_tag, average_len, max_len, total_len"
+    )?;
+    for ((key_tag, stored_value_tag), lengths) in trie_lengths {
+        if lengths.is_empty() {
+            continue;
+        }
+        let total_len = lengths.iter().sum::<usize>();
+        let average_len: usize = total_len / lengths.len();
+        let max_len: usize = *lengths.iter().max().unwrap();
+        writeln!(
+            report_writer,
+            "\"{}\", \"{}\", {}, {}, {}",
+            key_tag, stored_value_tag, average_len, max_len, total_len
+        )?;
+    }
+    Ok(())
+}
+
+/// Snapshot of an in-progress sequential TRIE_STORE walk, enough to resume
+/// exactly where it left off: the DFS frontier, the accumulated stats, and
+/// the era-info deletion progress. Encoded as flat length-prefixed fields
+/// (mirroring the `bytesrepr` style already used to read trie nodes) rather
+/// than pulling in a general serialization framework for one file format.
+struct Checkpoint {
+    state_root: Digest,
+    new_root_hash: Digest,
+    deleted_era_info: usize,
+    unvisited_nodes: Vec<Digest>,
+    key_tags: HashMap<String, usize>,
+    stored_value_tags: HashMap<String, usize>,
+    trie_lengths: HashMap<(String, String), Vec<usize>>,
+}
+
+fn write_u64<W: Write>(w: &mut W, value: u64) -> std::io::Result<()> {
+    w.write_all(&value.to_le_bytes())
+}
+
+fn read_u64<R: Read>(r: &mut R) -> std::io::Result<u64> {
+    let mut buf = [0u8; 8];
+    r.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+fn write_digest<W: Write>(w: &mut W, digest: &Digest) -> std::io::Result<()> {
+    w.write_all(digest.as_ref())
+}
+
+fn read_digest<R: Read>(r: &mut R) -> std::io::Result<Digest> {
+    let mut buf = [0u8; Digest::LENGTH];
+    r.read_exact(&mut buf)?;
+    Ok(Digest::from(buf))
+}
+
+fn write_string<W: Write>(w: &mut W, s: &str) -> std::io::Result<()> {
+    write_u64(w, s.len() as u64)?;
+    w.write_all(s.as_bytes())
+}
+
+fn read_string<R: Read>(r: &mut R) -> std::io::Result<String> {
+    let len = read_u64(r)? as usize;
+    let mut buf = vec![0u8; len];
+    r.read_exact(&mut buf)?;
+    String::from_utf8(buf).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+}
+
+/// Serializes the current walk state to `path` atomically (write to a temp
+/// file, then rename). Takes its fields by reference so callers on the hot
+/// path don't have to clone a potentially huge frontier/stats just to save a
+/// checkpoint.
+#[allow(clippy::too_many_arguments)]
+fn write_checkpoint(
+    path: &Path,
+    state_root: Digest,
+    new_root_hash: Digest,
+    deleted_era_info: usize,
+    unvisited_nodes: &[Digest],
+    key_tags: &HashMap<String, usize>,
+    stored_value_tags: &HashMap<String, usize>,
+    trie_lengths: &HashMap<(String, String), Vec<usize>>,
+) -> Result<(), anyhow::Error> {
+    let tmp_path = path.with_extension("tmp");
+    {
+        let mut w = BufWriter::new(File::create(&tmp_path)?);
+
+        write_digest(&mut w, &state_root)?;
+        write_digest(&mut w, &new_root_hash)?;
+        write_u64(&mut w, deleted_era_info as u64)?;
+
+        write_u64(&mut w, unvisited_nodes.len() as u64)?;
+        for digest in unvisited_nodes {
+            write_digest(&mut w, digest)?;
+        }
+
+        write_u64(&mut w, key_tags.len() as u64)?;
+        for (tag, count) in key_tags {
+            write_string(&mut w, tag)?;
+            write_u64(&mut w, *count as u64)?;
+        }
+
+        write_u64(&mut w, stored_value_tags.len() as u64)?;
+        for (tag, count) in stored_value_tags {
+            write_string(&mut w, tag)?;
+            write_u64(&mut w, *count as u64)?;
+        }
+
+        write_u64(&mut w, trie_lengths.len() as u64)?;
+        for ((key_tag, stored_value_tag), lengths) in trie_lengths {
+            write_string(&mut w, key_tag)?;
+            write_string(&mut w, stored_value_tag)?;
+            write_u64(&mut w, lengths.len() as u64)?;
+            for len in lengths {
+                write_u64(&mut w, *len as u64)?;
+            }
+        }
+
+        w.flush()?;
+    }
+    // Rename is atomic on the same filesystem, so a crash never leaves a
+    // torn checkpoint behind.
+    std::fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+fn read_checkpoint(path: &Path) -> Result<Checkpoint, anyhow::Error> {
+    let mut r = std::io::BufReader::new(File::open(path)?);
+
+    let state_root = read_digest(&mut r)?;
+    let new_root_hash = read_digest(&mut r)?;
+    let deleted_era_info = read_u64(&mut r)? as usize;
+
+    let unvisited_count = read_u64(&mut r)? as usize;
+    let mut unvisited_nodes = Vec::with_capacity(unvisited_count);
+    for _ in 0..unvisited_count {
+        unvisited_nodes.push(read_digest(&mut r)?);
+    }
+
+    let key_tags_count = read_u64(&mut r)? as usize;
+    let mut key_tags = HashMap::with_capacity(key_tags_count);
+    for _ in 0..key_tags_count {
+        let tag = read_string(&mut r)?;
+        let count = read_u64(&mut r)? as usize;
+        key_tags.insert(tag, count);
+    }
+
+    let stored_value_tags_count = read_u64(&mut r)? as usize;
+    let mut stored_value_tags = HashMap::with_capacity(stored_value_tags_count);
+    for _ in 0..stored_value_tags_count {
+        let tag = read_string(&mut r)?;
+        let count = read_u64(&mut r)? as usize;
+        stored_value_tags.insert(tag, count);
+    }
+
+    let trie_lengths_count = read_u64(&mut r)? as usize;
+    let mut trie_lengths = HashMap::with_capacity(trie_lengths_count);
+    for _ in 0..trie_lengths_count {
+        let key_tag = read_string(&mut r)?;
+        let stored_value_tag = read_string(&mut r)?;
+        let lengths_count = read_u64(&mut r)? as usize;
+        let mut lengths = Vec::with_capacity(lengths_count);
+        for _ in 0..lengths_count {
+            lengths.push(read_u64(&mut r)? as usize);
+        }
+        trie_lengths.insert((key_tag, stored_value_tag), lengths);
+    }
+
+    Ok(Checkpoint {
+        state_root,
+        new_root_hash,
+        deleted_era_info,
+        unvisited_nodes,
+        key_tags,
+        stored_value_tags,
+        trie_lengths,
+    })
+}
+
+/// Result of the parallel trie walk, in the shape the CSV
+/// report and era-info deletion pass both consume.
+struct TrieWalkResult {
+    key_tags: HashMap<String, usize>,
+    stored_value_tags: HashMap<String, usize>,
+    trie_lengths: HashMap<(String, String), Vec<usize>>,
+    era_info_keys: Vec<Key>,
+    visited_nodes: usize,
+    leaves_seen: usize,
+    bytes_processed: usize,
+}
+
+/// Walks the trie from `state_root` using `threads` worker threads sharing a
+/// single work queue. Each worker holds its own long-lived RO transaction
+/// (LMDB read transactions are cheap and safely concurrent) and accumulates
+/// stats in thread-local maps, merged once every worker is done to keep the
+/// hot path lock-free except for the shared queue.
+///
+/// `pending` tracks how many digests are currently either queued or being
+/// processed; it starts at 1 for `state_root`, is incremented by however
+/// many children a node pushes, and decremented once that node is done. A
+/// worker only exits once the queue is empty *and* `pending` has reached
+/// zero, so it can't race ahead of sibling workers still about to push more
+/// work.
+///
+/// EraInfo deletion mutates the trie root, so it is never done here: workers
+/// only collect the EraInfo keys they encounter, and the caller deletes them
+/// afterwards in a single-threaded pass.
+fn run_parallel_trie_walk(
+    env: &Environment,
+    db: lmdb::Database,
+    state_root: Digest,
+    threads: usize,
+    quiet: bool,
+    start: &Instant,
+) -> TrieWalkResult {
+    let queue = Arc::new(Mutex::new(vec![state_root]));
+    let pending = Arc::new(AtomicIsize::new(1));
+    let visited_nodes = Arc::new(AtomicUsize::new(0));
+    let leaves_seen = Arc::new(AtomicUsize::new(0));
+    let bytes_processed = Arc::new(AtomicUsize::new(0));
+
+    type WorkerOutput = (
+        HashMap<String, usize>,
+        HashMap<String, usize>,
+        HashMap<(String, String), Vec<usize>>,
+        Vec<Key>,
+    );
+
+    let worker_results: Vec<WorkerOutput> = std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..threads)
+            .map(|_| {
+                let queue = Arc::clone(&queue);
+                let pending = Arc::clone(&pending);
+                let visited_nodes = Arc::clone(&visited_nodes);
+                let leaves_seen = Arc::clone(&leaves_seen);
+                let bytes_processed = Arc::clone(&bytes_processed);
+                scope.spawn(move || {
+                    let txn = env.begin_ro_txn().expect("unable to begin RO txn");
+                    let mut key_tags = HashMap::<String, usize>::new();
+                    let mut stored_value_tags = HashMap::<String, usize>::new();
+                    let mut trie_lengths = HashMap::<(String, String), Vec<usize>>::new();
+                    let mut era_info_keys = Vec::<Key>::new();
+
+                    loop {
+                        let digest = queue.lock().unwrap().pop();
+                        let digest = match digest {
+                            Some(digest) => digest,
+                            None if pending.load(Ordering::SeqCst) <= 0 => break,
+                            None => {
+                                std::thread::sleep(Duration::from_millis(1));
+                                continue;
+                            }
+                        };
+
+                        let bytes = txn
+                            .get(db, &digest)
+                            .expect("provided state root does not exist in database");
+                        let byte_len = bytes.len();
+                        visited_nodes.fetch_add(1, Ordering::Relaxed);
+                        bytes_processed.fetch_add(byte_len, Ordering::Relaxed);
+
+                        let (trie_node, _remainder) = Trie::<Key, StoredValue>::from_bytes(bytes)
+                            .expect("unable to deserialize trie node");
+
+                        let mut children = Vec::new();
+                        match trie_node {
+                            Trie::Leaf {
+                                key: trie_key,
+                                value: trie_value,
+                            } => {
+                                leaves_seen.fetch_add(1, Ordering::Relaxed);
+                                log_trie_leaf_stats(
+                                    trie_key,
+                                    trie_value,
+                                    &mut key_tags,
+                                    &mut stored_value_tags,
+                                    &mut trie_lengths,
+                                    byte_len,
+                                );
+                                if let Key::EraInfo(_) = trie_key {
+                                    era_info_keys.push(trie_key);
+                                }
+                            }
+                            Trie::Node { pointer_block } => children.extend(
+                                pointer_block
+                                    .as_indexed_pointers()
+                                    .map(|(_, ptr)| ptr.into_hash()),
+                            ),
+                            Trie::Extension { affix: _, pointer } => {
+                                children.push(pointer.into_hash())
+                            }
+                        }
+
+                        if !children.is_empty() {
+                            pending.fetch_add(children.len() as isize, Ordering::SeqCst);
+                            queue.lock().unwrap().extend(children);
+                        }
+                        pending.fetch_sub(1, Ordering::SeqCst);
+                    }
+
+                    (key_tags, stored_value_tags, trie_lengths, era_info_keys)
+                })
+            })
+            .collect();
+
+        if !quiet {
+            let reporter_queue = Arc::clone(&queue);
+            let reporter_pending = Arc::clone(&pending);
+            let reporter_visited = Arc::clone(&visited_nodes);
+            let reporter_leaves = Arc::clone(&leaves_seen);
+            let reporter_bytes = Arc::clone(&bytes_processed);
+            scope.spawn(move || {
+                while reporter_pending.load(Ordering::SeqCst) > 0 {
+                    std::thread::sleep(PROGRESS_INTERVAL);
+                    println!(
+                        "progress: visited={} leaves={} frontier={} bytes={} elapsed={:?}",
+                        reporter_visited.load(Ordering::Relaxed),
+                        reporter_leaves.load(Ordering::Relaxed),
+                        reporter_queue.lock().unwrap().len(),
+                        reporter_bytes.load(Ordering::Relaxed),
+                        start.elapsed()
+                    );
+                }
+            });
+        }
+
+        handles
+            .into_iter()
+            .map(|handle| handle.join().expect("trie walk worker thread panicked"))
+            .collect()
+    });
+
+    let mut key_tags = HashMap::<String, usize>::new();
+    let mut stored_value_tags = HashMap::<String, usize>::new();
+    let mut trie_lengths = HashMap::<(String, String), Vec<usize>>::new();
+    let mut era_info_keys = Vec::<Key>::new();
+    for (worker_key_tags, worker_stored_value_tags, worker_trie_lengths, worker_era_info_keys) in
+        worker_results
+    {
+        for (tag, count) in worker_key_tags {
+            *key_tags.entry(tag).or_default() += count;
+        }
+        for (tag, count) in worker_stored_value_tags {
+            *stored_value_tags.entry(tag).or_default() += count;
+        }
+        for (tags, lengths) in worker_trie_lengths {
+            trie_lengths.entry(tags).or_default().extend(lengths);
+        }
+        era_info_keys.extend(worker_era_info_keys);
+    }
+
+    TrieWalkResult {
+        key_tags,
+        stored_value_tags,
+        trie_lengths,
+        era_info_keys,
+        visited_nodes: visited_nodes.load(Ordering::Relaxed),
+        leaves_seen: leaves_seen.load(Ordering::Relaxed),
+        bytes_processed: bytes_processed.load(Ordering::Relaxed),
+    }
+}
+
+/// Re-walks `source` from `root`, tallying the same `key_tag`/`stored_value_tag`
+/// counts the main stats walk produces, so an exported snapshot can be
+/// checked for parity against the original scan.
+fn walk_leaf_tag_counts(
+    source: &dyn TrieSource,
+    root: Digest,
+) -> (HashMap<String, usize>, HashMap<String, usize>) {
+    let mut key_tags = HashMap::<String, usize>::new();
+    let mut stored_value_tags = HashMap::<String, usize>::new();
+    let mut unvisited_nodes = vec![root];
+    while let Some(digest) = unvisited_nodes.pop() {
+        let bytes = source
+            .get(&digest)
+            .expect("exported snapshot is missing a node reachable from the state root");
+        let (trie_node, _remainder) =
+            Trie::<Key, StoredValue>::from_bytes(&bytes).expect("unable to deserialize trie node");
+        match trie_node {
+            Trie::Leaf {
+                key: trie_key,
+                value: trie_value,
+            } => {
+                *key_tags.entry(trie_key.type_string()).or_default() += 1;
+                *stored_value_tags.entry(trie_value.type_name()).or_default() += 1;
+            }
+            Trie::Node { pointer_block } => unvisited_nodes.extend(
+                pointer_block
+                    .as_indexed_pointers()
+                    .map(|(_, ptr)| ptr.into_hash()),
+            ),
+            Trie::Extension { affix: _, pointer } => unvisited_nodes.push(pointer.into_hash()),
+        }
+    }
+    (key_tags, stored_value_tags)
+}
+
+/// Deletes `trie_key` (an EraInfo entry) from the trie rooted at `root`,
+/// returning the resulting root. EraInfo deletion mutates the trie root, so
+/// every caller must run it serially against a single, non-moving root: the
+/// sequential walk does this inline as each EraInfo leaf is visited, and the
+/// parallel walk collects EraInfo keys during the concurrent walk and runs
+/// this afterward in a single-threaded pass.
+fn delete_era_info_key(
+    engine_state: &mut EngineState<LmdbGlobalState>,
+    root: Digest,
+    trie_key: Key,
+) -> Digest {
+    match engine_state.delete_key(CorrelationId::new(), root, &trie_key) {
+        Ok(DeleteResult::Deleted(new_root)) => new_root,
+        Ok(delete) => panic!("failed to delete key {:?} - {:?}", trie_key, delete),
+        err => panic!("failed to delete key {:?} - {:?}", trie_key, err),
+    }
+}
+
 fn log_trie_leaf_stats(
     trie_key: Key,
     trie_value: StoredValue,
@@ -211,3 +960,91 @@ fn log_trie_leaf_stats(
     let trie_length_values = trie_lengths.entry((key_tag, stored_value_tag)).or_default();
     trie_length_values.push(byte_len);
 }
+
+/// Reclaims `TRIE_STORE` nodes that are unreachable from `retain_roots`.
+///
+/// MARK: DFS from every retained root (same traversal as the stats walk),
+/// accumulating every visited digest into `live`. SWEEP: scan every stored
+/// key and buffer the ones missing from `live`, then delete them in batched
+/// write transactions. Sweeping from a live RO cursor would deadlock/observe
+/// a moving target, so the dead set is collected up front; since dead nodes
+/// are by definition unreferenced, committing in batches leaves the store
+/// consistent even if interrupted partway through.
+fn run_gc(
+    env: &Environment,
+    db: lmdb::Database,
+    retain_roots: &[Digest],
+) -> Result<(), anyhow::Error> {
+    let mark_start = Instant::now();
+    let txn = env.begin_ro_txn()?;
+    let mut live = HashSet::<Digest>::new();
+    let mut unvisited_nodes: Vec<Digest> = retain_roots.to_vec();
+
+    while let Some(digest) = unvisited_nodes.pop() {
+        if !live.insert(digest) {
+            continue;
+        }
+        let bytes = txn
+            .get(db, &digest)
+            .expect("retained root or a node reachable from it does not exist in database");
+        let (trie_node, _remainder) =
+            Trie::<Key, StoredValue>::from_bytes(bytes).expect("unable to deserialize trie node");
+        match trie_node {
+            Trie::Leaf { .. } => {}
+            Trie::Node { pointer_block } => unvisited_nodes.extend(
+                pointer_block
+                    .as_indexed_pointers()
+                    .map(|(_, ptr)| ptr.into_hash()),
+            ),
+            Trie::Extension { affix: _, pointer } => unvisited_nodes.push(pointer.into_hash()),
+        }
+    }
+    drop(txn);
+    println!(
+        "MARK: {} live node(s) reachable from {} retained root(s) in {:?}",
+        live.len(),
+        retain_roots.len(),
+        mark_start.elapsed()
+    );
+
+    let sweep_start = Instant::now();
+    let txn = env.begin_ro_txn()?;
+    let mut dead = Vec::<(Digest, usize)>::new();
+    {
+        let mut cursor = txn.open_ro_cursor(db)?;
+        for (key, value) in cursor.iter() {
+            let digest = Digest::try_from(key).expect("stored key is not a valid digest");
+            if !live.contains(&digest) {
+                dead.push((digest, value.len()));
+            }
+        }
+    }
+    drop(txn);
+    println!("SWEEP: {} dead node(s) to reclaim", dead.len());
+
+    const BATCH_SIZE: usize = 10_000;
+    let mut reclaimed_records = 0usize;
+    let mut reclaimed_bytes = 0usize;
+    for batch in dead.chunks(BATCH_SIZE) {
+        let mut write_txn = env.begin_rw_txn()?;
+        for (digest, len) in batch {
+            match write_txn.del(db, digest, None) {
+                Ok(()) => {
+                    reclaimed_records += 1;
+                    reclaimed_bytes += len;
+                }
+                Err(lmdb::Error::NotFound) => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        write_txn.commit()?;
+    }
+
+    println!(
+        "GC reclaimed {} record(s) / {} bytes in {:?}",
+        reclaimed_records,
+        reclaimed_bytes,
+        sweep_start.elapsed()
+    );
+    Ok(())
+}
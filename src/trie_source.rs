@@ -0,0 +1,117 @@
+//! Generic key-value access over whatever engine a trie happens to be stored
+//! in, so the diagnostic walk and stats logic don't need to know they're
+//! talking to LMDB. Mirrors the generic-KV-over-concrete-driver split other
+//! chains use to keep storage-layer migrations out of their tooling.
+
+use std::path::Path;
+
+use casper_hashing::Digest;
+use lmdb::{Cursor, Environment, RoCursor, RoTransaction, Transaction};
+
+/// A read-only view over a trie's underlying key-value store, keyed by
+/// content-addressed `Digest`.
+///
+/// Implementations return owned bytes rather than borrowed slices: LMDB ties
+/// returned data to the lifetime of its read transaction, while other
+/// engines (e.g. RocksDB) hand back owned buffers already, so owned bytes is
+/// the lowest common denominator that lets both live behind one trait
+/// object.
+pub trait TrieSource {
+    /// Fetches the raw bytes stored under `digest`, if present.
+    fn get(&self, digest: &Digest) -> Option<Vec<u8>>;
+
+    /// Iterates every `(key, value)` pair in the store.
+    fn scan(&self) -> Box<dyn Iterator<Item = (Vec<u8>, Vec<u8>)> + '_>;
+}
+
+/// `TrieSource` over an already-open LMDB environment and database handle.
+///
+/// Holds a single read transaction for the lifetime of the source, reused by
+/// every `get`/`scan` call, rather than opening a fresh one per call: a full
+/// walk calls `get` once per visited trie node, and LMDB read transactions
+/// are not free at that scale.
+pub struct LmdbSource {
+    // SAFETY: `txn` borrows `env` for LMDB's `'env` lifetime, which is erased
+    // to `'static` here so the two can live in the same struct. This is sound
+    // because struct fields drop in declaration order: `txn` (and the FFI
+    // handle it closes on drop) always drops before `env` does.
+    txn: RoTransaction<'static>,
+    env: Environment,
+    db: lmdb::Database,
+}
+
+impl LmdbSource {
+    pub fn new(env: Environment, db: lmdb::Database) -> Self {
+        let txn = env.begin_ro_txn().expect("unable to begin RO txn");
+        // SAFETY: see the field comment on `txn` above.
+        let txn: RoTransaction<'static> = unsafe { std::mem::transmute(txn) };
+        LmdbSource { txn, env, db }
+    }
+}
+
+impl TrieSource for LmdbSource {
+    fn get(&self, digest: &Digest) -> Option<Vec<u8>> {
+        self.txn.get(self.db, digest).ok().map(<[u8]>::to_vec)
+    }
+
+    fn scan(&self) -> Box<dyn Iterator<Item = (Vec<u8>, Vec<u8>)> + '_> {
+        let mut cursor = self
+            .txn
+            .open_ro_cursor(self.db)
+            .expect("unable to open cursor");
+        let iter = cursor.iter();
+        Box::new(LmdbScanIter {
+            iter,
+            _cursor: cursor,
+        })
+    }
+}
+
+/// Streams `(key, value)` pairs lazily off a cursor instead of collecting the
+/// whole database into memory up front. Bundles the cursor alongside its
+/// iterator so the cursor's FFI handle stays alive for as long as the
+/// iterator is used.
+struct LmdbScanIter<'txn> {
+    iter: lmdb::Iter<'txn>,
+    _cursor: RoCursor<'txn>,
+}
+
+impl<'txn> Iterator for LmdbScanIter<'txn> {
+    type Item = (Vec<u8>, Vec<u8>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (key, value) = self.iter.next()?.expect("lmdb cursor iteration failed");
+        Some((key.to_vec(), value.to_vec()))
+    }
+}
+
+/// `TrieSource` over a RocksDB-backed trie store, for operators who have
+/// migrated their trie off LMDB.
+pub struct RocksDbSource {
+    db: rocksdb::DB,
+}
+
+impl RocksDbSource {
+    /// Opens `path` read-only, matching `LmdbSource`'s read-only transactions:
+    /// `DB::open_default` would open read-write, silently creating an empty
+    /// store for a typo'd path and taking RocksDB's exclusive process lock,
+    /// which would stop this diagnostic tool from running alongside the node
+    /// or another diagnostic run against the same store.
+    pub fn open(path: &Path) -> Result<Self, rocksdb::Error> {
+        let db = rocksdb::DB::open_for_read_only(&rocksdb::Options::default(), path, false)?;
+        Ok(RocksDbSource { db })
+    }
+}
+
+impl TrieSource for RocksDbSource {
+    fn get(&self, digest: &Digest) -> Option<Vec<u8>> {
+        self.db.get(digest.as_ref()).expect("rocksdb get failed")
+    }
+
+    fn scan(&self) -> Box<dyn Iterator<Item = (Vec<u8>, Vec<u8>)> + '_> {
+        Box::new(self.db.iterator(rocksdb::IteratorMode::Start).map(|entry| {
+            let (key, value) = entry.expect("rocksdb iteration failed");
+            (key.to_vec(), value.to_vec())
+        }))
+    }
+}